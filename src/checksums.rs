@@ -0,0 +1,52 @@
+use std::{collections::HashMap, path::Path};
+
+/// Loads a `sha256sum`-style sidecar file (`<hex digest>  <name>` per line)
+/// mapping a scraped [`crate::link::Link::name`] to its expected SHA-256
+/// digest.
+pub async fn load(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let contents = tokio::fs::read_to_string(path).await?;
+    Ok(parse(&contents))
+}
+
+fn parse(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (digest, name) = line.split_once(char::is_whitespace)?;
+            let name = name.trim().trim_start_matches('*');
+            Some((name.to_owned(), digest.trim().to_lowercase()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sha256sum_style_lines() {
+        let contents = "\
+e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  foo.zip
+2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824 *bar.jpg
+";
+
+        let map = parse(contents);
+
+        assert_eq!(
+            map.get("foo.zip").map(String::as_str),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+        assert_eq!(
+            map.get("bar.jpg").map(String::as_str),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+    }
+
+    #[test]
+    fn ignores_blank_and_malformed_lines() {
+        let contents = "\n   \nnotadigest\n";
+
+        assert!(parse(contents).is_empty());
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    cmp::min,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use futures_util::StreamExt;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rand::Rng;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, ETAG, LAST_MODIFIED, RANGE},
+    Response, StatusCode,
+};
+use sha2::{Digest, Sha256};
+use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
+use url::Url;
+
+use crate::error::DownloadError;
+use crate::filename::{self, has_extension};
+use crate::link::Link;
+
+/// Base delay for the first retry; doubled on each subsequent attempt, plus
+/// jitter, up to [`MAX_BACKOFF`].
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Names already claimed by a download this run (claims are never released),
+/// so that two links resolving to the same file name don't clobber each other.
+pub type ClaimedNames = Arc<Mutex<HashSet<String>>>;
+
+/// How to treat a file that already exists at the destination path.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// Never clobber an existing file: resume a partial one, skip a
+    /// complete one.
+    Never,
+    /// Always re-download from scratch, ignoring whatever is on disk.
+    Always,
+    /// Resume a partial file as [`OverwritePolicy::Never`] would. For a
+    /// complete file, re-download from scratch if the server's
+    /// `Last-Modified` is newer than the local file's mtime, or if either
+    /// timestamp is unknown; otherwise skip it.
+    IfNewer,
+}
+
+/// Outcome of a single [`download_file_to`] call, used to aggregate a summary
+/// across the whole batch instead of printing raw per-task results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadStatus {
+    /// A complete copy was already on disk; nothing was transferred.
+    Exists,
+    /// A partial copy was on disk and the remaining bytes were fetched via
+    /// `Range`.
+    Resumed,
+    /// The file was downloaded from scratch.
+    Downloaded,
+}
+
+/// Result of a [`download_file_to`] call: what happened, and how many bytes
+/// were actually transferred (0 for [`DownloadStatus::Exists`]).
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadOutcome {
+    pub status: DownloadStatus,
+    pub bytes_transferred: u64,
+}
+
+/// Downloads `link` into `dir`, resuming a partial file if one is present and
+/// skipping the transfer entirely if a complete copy already exists. Retries
+/// transient failures (connection resets, timeouts, `5xx` responses, and
+/// integrity mismatches) up to `max_retries` times with exponential backoff
+/// and jitter, continuing from the bytes already written on each attempt;
+/// under [`OverwritePolicy::Always`] every attempt starts over from zero.
+pub async fn download_file_to(
+    link: &Link,
+    dir: impl AsRef<Path>,
+    mb: MultiProgress,
+    p_sty: ProgressStyle,
+    claimed_names: ClaimedNames,
+    max_retries: u32,
+    overwrite: OverwritePolicy,
+) -> anyhow::Result<DownloadOutcome> {
+    let client = reqwest::Client::new();
+
+    // Only bother the server for naming hints if the scraped name is
+    // unusable; a HEAD response fetched here is reused below for the
+    // resume/skip check as well. Retried like any other request so a
+    // transient hiccup here doesn't permanently kill the download before it
+    // even starts.
+    let head = if has_extension(&link.name) {
+        None
+    } else {
+        Some(head_with_retry(&client, &link.url, max_retries).await?)
+    };
+
+    let name = filename::resolve(&link.name, &link.url, head.as_ref());
+    // Claimed once, up front: retries reuse the same name/path rather than
+    // claiming a fresh `(1)`-suffixed one on every attempt.
+    let name = filename::claim_unique(name, &claimed_names).await;
+    let file_path = PathBuf::from(dir.as_ref()).join(Path::new(&name));
+
+    let pb = mb.add(ProgressBar::new(0));
+    pb.set_style(p_sty);
+
+    let ctx = AttemptContext {
+        client: &client,
+        link,
+        name: &name,
+        file_path: &file_path,
+        head: head.as_ref(),
+        pb: &pb,
+        overwrite,
+    };
+
+    let mut attempt = 0u32;
+
+    loop {
+        attempt += 1;
+
+        match attempt_download(&ctx, attempt).await {
+            Ok(outcome) => return Ok(outcome),
+            Err(err) if attempt <= max_retries && is_retryable(&err) => {
+                let backoff = backoff_with_jitter(attempt);
+                pb.set_message(format!(
+                    "{name}: attempt {attempt} failed ({err}), retrying in {backoff:?}"
+                ));
+                tokio::time::sleep(backoff).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Issues a `HEAD` request, retrying connection-level hiccups (the same
+/// predicate used for the GET/stream path, see [`is_retryable`]) up to
+/// `max_retries` times with the usual backoff and jitter.
+async fn head_with_retry(
+    client: &reqwest::Client,
+    url: &Url,
+    max_retries: u32,
+) -> Result<Response, reqwest::Error> {
+    let mut attempt = 0u32;
+
+    loop {
+        attempt += 1;
+
+        match client.head(url.clone()).headers(get_headers()).send().await {
+            Ok(resp) => return Ok(resp),
+            Err(err)
+                if attempt <= max_retries
+                    && (err.is_timeout() || err.is_connect() || err.is_request()) =>
+            {
+                tokio::time::sleep(backoff_with_jitter(attempt)).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Parameters shared across every retry attempt for a single [`Link`], so
+/// [`attempt_download`] doesn't need one argument per field.
+#[derive(Clone, Copy)]
+struct AttemptContext<'a> {
+    client: &'a reqwest::Client,
+    link: &'a Link,
+    name: &'a str,
+    file_path: &'a Path,
+    head: Option<&'a Response>,
+    pb: &'a ProgressBar,
+    overwrite: OverwritePolicy,
+}
+
+/// A single attempt at downloading `ctx.link` to `ctx.file_path`, picking up
+/// from whatever bytes (if any) a previous attempt already wrote to disk.
+/// `ctx.head`, if present, is the response from an earlier `HEAD` made while
+/// resolving the file name, reused here to avoid a redundant request.
+async fn attempt_download(ctx: &AttemptContext<'_>, attempt: u32) -> anyhow::Result<DownloadOutcome> {
+    let AttemptContext {
+        client,
+        link,
+        name,
+        file_path,
+        head,
+        pb,
+        overwrite,
+    } = *ctx;
+
+    let metadata = tokio::fs::metadata(file_path).await.ok();
+    let on_disk_len = metadata.as_ref().map(|m| m.len()).filter(|len| *len > 0);
+
+    // If we don't already have a HEAD response from name resolution, fetch
+    // one now: `Never` needs it to compare content-length, `IfNewer` needs
+    // it for `Last-Modified`. `Always` never consults the server about the
+    // existing file, so it's skipped there.
+    let fallback_head;
+    let head = if on_disk_len.is_some() && head.is_none() && overwrite != OverwritePolicy::Always {
+        fallback_head = Some(
+            client
+                .head(link.url.clone())
+                .headers(get_headers())
+                .send()
+                .await?,
+        );
+        fallback_head.as_ref()
+    } else {
+        head
+    };
+
+    let existing_len = match resume_decision(
+        overwrite,
+        on_disk_len,
+        metadata.as_ref().and_then(|m| m.modified().ok()),
+        head.and_then(Response::content_length),
+        head.and_then(last_modified),
+    ) {
+        ResumeDecision::Skip => {
+            return Ok(DownloadOutcome {
+                status: DownloadStatus::Exists,
+                bytes_transferred: 0,
+            });
+        }
+        ResumeDecision::Download(existing_len) => existing_len,
+    };
+
+    let etag_before = head.and_then(etag);
+
+    let resumable_from = existing_len.unwrap_or(0);
+
+    let mut headers = get_headers();
+    if resumable_from > 0 {
+        headers.insert(RANGE, HeaderValue::from_str(&format!("bytes={resumable_from}-"))?);
+    }
+
+    let resp = client.get(link.url.clone()).headers(headers).send().await?;
+
+    if !resp.status().is_success() {
+        anyhow::bail!(DownloadError::ServerError {
+            name: name.to_owned(),
+            status: resp.status(),
+        });
+    }
+
+    let mut hasher = link.checksum.is_some().then(Sha256::new);
+
+    let (mut file, mut downloaded, status) = if resumable_from > 0 && resp.status() == StatusCode::PARTIAL_CONTENT {
+        let file = File::options().append(true).open(file_path).await?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(tokio::fs::read(file_path).await?);
+        }
+        (file, resumable_from, DownloadStatus::Resumed)
+    } else {
+        // Either this is a fresh download, or the server ignored our `Range`
+        // header (plain `200 OK`); either way, start over from zero.
+        let file = File::create(file_path).await?;
+        (file, 0, DownloadStatus::Downloaded)
+    };
+
+    let total_size = downloaded + resp.content_length().unwrap_or(0);
+    pb.set_length(total_size);
+    pb.set_message(format!("Downloading {name} (attempt {attempt})"));
+    pb.set_position(downloaded);
+
+    let etag_after = etag(&resp);
+    let mut stream = resp.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
+        }
+        file.write_all(&chunk).await?;
+
+        // `total_size` may be 0 when the server didn't send a
+        // `Content-Length` (e.g. chunked transfer-encoding): track the real
+        // byte count separately so it doesn't get clamped to a bogus total,
+        // and only clamp what we hand to the progress bar.
+        downloaded += chunk.len() as u64;
+        pb.set_position(min(downloaded, total_size));
+    }
+    file.flush().await?;
+
+    verify_integrity(IntegrityCheck {
+        link,
+        name,
+        file_path,
+        hasher,
+        downloaded,
+        total_size,
+        etag_before,
+        etag_after,
+    })
+    .await?;
+
+    pb.finish_with_message("done");
+
+    Ok(DownloadOutcome {
+        status,
+        bytes_transferred: downloaded - resumable_from,
+    })
+}
+
+/// What to do with a file already on disk, given what we know about the
+/// local copy and (if fetched) the remote one.
+enum ResumeDecision {
+    /// The on-disk copy is already complete and up to date: skip the
+    /// transfer entirely.
+    Skip,
+    /// (Re)download, resuming from the given byte offset if `Some` (i.e. a
+    /// partial file is present), or from scratch if `None`.
+    Download(Option<u64>),
+}
+
+/// Decides what [`attempt_download`] should do with whatever is already on
+/// disk at `file_path`, given `overwrite` and what the server told us via
+/// `HEAD` (if anything). Pure so it can be unit tested without a real
+/// `reqwest::Response` or `std::fs::Metadata`.
+fn resume_decision(
+    overwrite: OverwritePolicy,
+    on_disk_len: Option<u64>,
+    local_modified: Option<std::time::SystemTime>,
+    remote_content_length: Option<u64>,
+    remote_last_modified: Option<std::time::SystemTime>,
+) -> ResumeDecision {
+    match overwrite {
+        OverwritePolicy::Always => ResumeDecision::Download(None),
+        OverwritePolicy::Never => {
+            if on_disk_len.is_some() && remote_content_length == on_disk_len {
+                return ResumeDecision::Skip;
+            }
+            ResumeDecision::Download(on_disk_len)
+        }
+        OverwritePolicy::IfNewer => {
+            // `Last-Modified` only means anything once we know the file on
+            // disk is actually complete; a partial file left by an
+            // interrupted run has a "recent" mtime that would otherwise
+            // always lose to the remote's, permanently stranding it.
+            let is_complete = on_disk_len.is_some() && remote_content_length == on_disk_len;
+
+            if !is_complete {
+                // Partial (or missing): resume from what's already on disk,
+                // same as `Never`.
+                return ResumeDecision::Download(on_disk_len);
+            }
+
+            let remote_is_newer = local_modified.is_none_or(|local| {
+                remote_last_modified.is_none_or(|remote| remote > local)
+            });
+
+            if remote_is_newer {
+                // Complete but stale: (re)download from scratch.
+                ResumeDecision::Download(None)
+            } else {
+                ResumeDecision::Skip
+            }
+        }
+    }
+}
+
+/// Whether `err` is worth retrying: a corrupt download (caught by
+/// [`verify_integrity`]), a `5xx` response, or a connection-level hiccup —
+/// but not a `4xx` response, which means the link itself is dead (expired,
+/// private, never existed) and retrying can only waste time.
+fn is_retryable(err: &anyhow::Error) -> bool {
+    match err.downcast_ref::<DownloadError>() {
+        Some(DownloadError::ServerError { status, .. }) => return status.is_server_error(),
+        Some(_) => return true,
+        None => {}
+    }
+
+    err.downcast_ref::<reqwest::Error>().is_some_and(|err| {
+        err.is_timeout() || err.is_connect() || err.is_request() || err.is_body()
+    })
+}
+
+/// Exponential backoff (`BASE_BACKOFF * 2^(attempt - 1)`, capped at
+/// `MAX_BACKOFF`) plus up to 250ms of jitter, so a fleet of retrying tasks
+/// doesn't hammer the server in lockstep.
+fn backoff_with_jitter(attempt: u32) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(6);
+    let base = (BASE_BACKOFF * 2u32.pow(exponent)).min(MAX_BACKOFF);
+    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..250));
+
+    base + jitter
+}
+
+/// The inputs [`verify_integrity`] needs to judge a single completed
+/// download, bundled into one struct rather than one argument per field.
+struct IntegrityCheck<'a> {
+    link: &'a Link,
+    name: &'a str,
+    file_path: &'a Path,
+    hasher: Option<Sha256>,
+    downloaded: u64,
+    total_size: u64,
+    etag_before: Option<String>,
+    etag_after: Option<String>,
+}
+
+/// Checks the just-downloaded file against what we expected it to be,
+/// deleting it and returning a [`DownloadError`] on any mismatch so the
+/// caller can retry rather than leave a corrupt file on disk.
+async fn verify_integrity(check: IntegrityCheck<'_>) -> Result<(), DownloadError> {
+    let IntegrityCheck {
+        link,
+        name,
+        file_path,
+        hasher,
+        downloaded,
+        total_size,
+        etag_before,
+        etag_after,
+    } = check;
+
+    let mismatch = if let Some(expected) = &link.checksum {
+        let actual = hex::encode(hasher.expect("hasher is set whenever link.checksum is").finalize());
+        (actual != *expected).then(|| DownloadError::ChecksumMismatch {
+            name: name.to_owned(),
+            expected: expected.clone(),
+            actual,
+        })
+    } else if total_size > 0 && downloaded != total_size {
+        Some(DownloadError::SizeMismatch {
+            name: name.to_owned(),
+            expected: total_size,
+            actual: downloaded,
+        })
+    } else {
+        etag_before
+            .zip(etag_after)
+            .filter(|(before, after)| before != after)
+            .map(|(before, after)| DownloadError::ResourceChanged {
+                name: name.to_owned(),
+                before,
+                after,
+            })
+    };
+
+    match mismatch {
+        Some(err) => {
+            let _ = tokio::fs::remove_file(file_path).await;
+            Err(err)
+        }
+        None => Ok(()),
+    }
+}
+
+fn etag(resp: &Response) -> Option<String> {
+    resp.headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn last_modified(resp: &Response) -> Option<std::time::SystemTime> {
+    let raw = resp.headers().get(LAST_MODIFIED)?.to_str().ok()?;
+    httpdate::parse_http_date(raw).ok()
+}
+
+pub(crate) fn get_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    // pretend we're a browser
+    headers.insert(
+        HeaderName::from_static("user-agent"),
+        HeaderValue::from_static("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36"));
+
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_with_jitter_doubles_each_attempt() {
+        for attempt in 1..=4 {
+            let backoff = backoff_with_jitter(attempt);
+            let min = BASE_BACKOFF * 2u32.pow(attempt - 1);
+            let max = min + Duration::from_millis(250);
+
+            assert!(
+                backoff >= min && backoff < max,
+                "attempt {attempt}: {backoff:?} not in [{min:?}, {max:?})"
+            );
+        }
+    }
+
+    #[test]
+    fn backoff_with_jitter_is_capped_at_max_backoff() {
+        let backoff = backoff_with_jitter(20);
+        assert!(backoff >= MAX_BACKOFF);
+        assert!(backoff < MAX_BACKOFF + Duration::from_millis(250));
+    }
+
+    fn test_link(checksum: Option<&str>) -> Link {
+        Link {
+            url: Url::parse("https://example.com/f").unwrap(),
+            name: "f".to_owned(),
+            checksum: checksum.map(str::to_owned),
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_integrity_ok_when_checksum_matches() {
+        let link = test_link(Some(
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+        ));
+        let mut hasher = Sha256::new();
+        hasher.update(b"hello world");
+
+        let result = verify_integrity(IntegrityCheck {
+            link: &link,
+            name: "f",
+            file_path: Path::new("/tmp/neboder-test-does-not-exist"),
+            hasher: Some(hasher),
+            downloaded: 11,
+            total_size: 11,
+            etag_before: None,
+            etag_after: None,
+        })
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn verify_integrity_detects_checksum_mismatch() {
+        let link = test_link(Some("deadbeef"));
+        let mut hasher = Sha256::new();
+        hasher.update(b"hello world");
+
+        let result = verify_integrity(IntegrityCheck {
+            link: &link,
+            name: "f",
+            file_path: Path::new("/tmp/neboder-test-does-not-exist"),
+            hasher: Some(hasher),
+            downloaded: 11,
+            total_size: 11,
+            etag_before: None,
+            etag_after: None,
+        })
+        .await;
+
+        assert!(matches!(result, Err(DownloadError::ChecksumMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn verify_integrity_detects_size_mismatch_without_checksum() {
+        let link = test_link(None);
+
+        let result = verify_integrity(IntegrityCheck {
+            link: &link,
+            name: "f",
+            file_path: Path::new("/tmp/neboder-test-does-not-exist"),
+            hasher: None,
+            downloaded: 5,
+            total_size: 10,
+            etag_before: None,
+            etag_after: None,
+        })
+        .await;
+
+        assert!(matches!(result, Err(DownloadError::SizeMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn verify_integrity_detects_etag_change_without_checksum_or_size() {
+        let link = test_link(None);
+
+        let result = verify_integrity(IntegrityCheck {
+            link: &link,
+            name: "f",
+            file_path: Path::new("/tmp/neboder-test-does-not-exist"),
+            hasher: None,
+            downloaded: 10,
+            total_size: 10,
+            etag_before: Some("\"a\"".to_owned()),
+            etag_after: Some("\"b\"".to_owned()),
+        })
+        .await;
+
+        assert!(matches!(result, Err(DownloadError::ResourceChanged { .. })));
+    }
+
+    #[tokio::test]
+    async fn verify_integrity_ok_when_nothing_to_check_against() {
+        let link = test_link(None);
+
+        let result = verify_integrity(IntegrityCheck {
+            link: &link,
+            name: "f",
+            file_path: Path::new("/tmp/neboder-test-does-not-exist"),
+            hasher: None,
+            downloaded: 10,
+            total_size: 10,
+            etag_before: None,
+            etag_after: None,
+        })
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn resume_decision_never_skips_when_sizes_match() {
+        let decision = resume_decision(OverwritePolicy::Never, Some(10), None, Some(10), None);
+        assert!(matches!(decision, ResumeDecision::Skip));
+    }
+
+    #[test]
+    fn resume_decision_never_resumes_when_sizes_differ() {
+        let decision = resume_decision(OverwritePolicy::Never, Some(4), None, Some(10), None);
+        assert!(matches!(decision, ResumeDecision::Download(Some(4))));
+    }
+
+    #[test]
+    fn resume_decision_never_downloads_fresh_when_nothing_on_disk() {
+        let decision = resume_decision(OverwritePolicy::Never, None, None, Some(10), None);
+        assert!(matches!(decision, ResumeDecision::Download(None)));
+    }
+
+    #[test]
+    fn resume_decision_always_ignores_disk_state() {
+        let decision = resume_decision(OverwritePolicy::Always, Some(10), None, Some(10), None);
+        assert!(matches!(decision, ResumeDecision::Download(None)));
+    }
+
+    #[test]
+    fn resume_decision_if_newer_skips_complete_file_when_remote_not_newer() {
+        let local = std::time::UNIX_EPOCH + Duration::from_secs(2000);
+        let remote = std::time::UNIX_EPOCH + Duration::from_secs(1000);
+        let decision =
+            resume_decision(OverwritePolicy::IfNewer, Some(10), Some(local), Some(10), Some(remote));
+        assert!(matches!(decision, ResumeDecision::Skip));
+    }
+
+    #[test]
+    fn resume_decision_if_newer_redownloads_complete_file_when_remote_is_newer() {
+        let local = std::time::UNIX_EPOCH + Duration::from_secs(1000);
+        let remote = std::time::UNIX_EPOCH + Duration::from_secs(2000);
+        let decision =
+            resume_decision(OverwritePolicy::IfNewer, Some(10), Some(local), Some(10), Some(remote));
+        assert!(matches!(decision, ResumeDecision::Download(None)));
+    }
+
+    #[test]
+    fn resume_decision_if_newer_resumes_partial_file_regardless_of_mtime() {
+        // A partial file has a "recent" mtime that would naively look newer
+        // than the remote's Last-Modified; it must still be resumed rather
+        // than permanently treated as up to date.
+        let local = std::time::UNIX_EPOCH + Duration::from_secs(2000);
+        let remote = std::time::UNIX_EPOCH + Duration::from_secs(1000);
+        let decision =
+            resume_decision(OverwritePolicy::IfNewer, Some(4), Some(local), Some(10), Some(remote));
+        assert!(matches!(decision, ResumeDecision::Download(Some(4))));
+    }
+}
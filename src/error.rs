@@ -0,0 +1,32 @@
+use thiserror::Error;
+
+/// Errors specific to downloading a single file (an integrity mismatch found
+/// after the transfer, or an unexpected HTTP status), kept distinct from the
+/// generic [`anyhow::Error`] used elsewhere so the retry layer can tell them
+/// apart from any other failure: mismatches are always retried, a
+/// [`DownloadError::ServerError`] only for a `5xx` status.
+#[derive(Debug, Error)]
+pub enum DownloadError {
+    #[error("checksum mismatch for {name}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        name: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("size mismatch for {name}: expected {expected} bytes, got {actual} bytes")]
+    SizeMismatch { name: String, expected: u64, actual: u64 },
+
+    #[error("{name} changed on the server mid-download (ETag went from {before} to {after})")]
+    ResourceChanged {
+        name: String,
+        before: String,
+        after: String,
+    },
+
+    #[error("unexpected HTTP status {status} for {name}")]
+    ServerError {
+        name: String,
+        status: reqwest::StatusCode,
+    },
+}
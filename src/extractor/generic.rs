@@ -0,0 +1,64 @@
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use scraper::{Html, Selector};
+use url::Url;
+
+use crate::link::Link;
+
+use super::{resolve_href, Extractor};
+
+/// File extensions treated as "downloadable" by [`GenericExtractor`].
+const FILE_EXTENSIONS: &[&str] = &[
+    "zip", "tar", "gz", "7z", "rar", "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "jpg",
+    "jpeg", "png", "gif", "bmp", "tiff", "webp", "mp3", "mp4", "mov", "avi", "mkv", "wav", "flac",
+    "csv", "json", "txt",
+];
+
+/// Fallback extractor for pages with no known layout: collects every `<a
+/// href>` whose path ends in a recognized file extension, using the link
+/// text (or the last path segment if the text is empty) as the file name.
+pub struct GenericExtractor;
+
+#[async_trait(?Send)]
+impl Extractor for GenericExtractor {
+    async fn extract(&self, html: &Html, base_url: &Url) -> anyhow::Result<BTreeSet<Link>> {
+        let selector = Selector::parse("a").expect("Could not create 'a' selector");
+
+        Ok(html
+            .select(&selector)
+            .filter_map(|el| {
+                let href = el.value().attr("href")?;
+                let url = resolve_href(base_url, href)?;
+                has_known_extension(&url).then_some((el, url))
+            })
+            .map(|(el, url)| {
+                let name = el.text().collect::<String>().trim().to_owned();
+                let name = if name.is_empty() {
+                    last_path_segment(&url)
+                } else {
+                    name
+                };
+
+                Link {
+                    url,
+                    name,
+                    checksum: None,
+                }
+            })
+            .collect())
+    }
+}
+
+fn has_known_extension(url: &Url) -> bool {
+    last_path_segment(url)
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| FILE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+fn last_path_segment(url: &Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .to_owned()
+}
@@ -0,0 +1,89 @@
+pub mod generic;
+pub mod upenn;
+
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use scraper::Html;
+use url::Url;
+
+use crate::link::Link;
+
+/// Scrapes a fetched page for the set of files it links to.
+///
+/// Implementations are intentionally decoupled from the HTTP fetch step: they
+/// are handed the already-parsed document and the URL it was fetched from (so
+/// relative `href`s can be resolved), and return the links they found.
+#[async_trait(?Send)]
+pub trait Extractor {
+    async fn extract(&self, html: &Html, base_url: &Url) -> anyhow::Result<BTreeSet<Link>>;
+}
+
+/// Which built-in [`Extractor`] to use, selectable from the CLI.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExtractorKind {
+    /// Pick an extractor based on the target URL's host, falling back to
+    /// [`ExtractorKind::Generic`] if the host isn't recognized.
+    Auto,
+    /// The original `.nidb-album` layout used by `web.sas.upenn.edu`.
+    Upenn,
+    /// Collect every `<a href>` whose path ends in a known file extension.
+    Generic,
+}
+
+impl ExtractorKind {
+    /// Resolves this selection to a concrete [`Extractor`], consulting
+    /// `base_url`'s host when `self` is [`ExtractorKind::Auto`].
+    pub fn resolve(self, base_url: &Url) -> Box<dyn Extractor> {
+        match self {
+            ExtractorKind::Upenn => Box::new(upenn::UpennExtractor),
+            ExtractorKind::Generic => Box::new(generic::GenericExtractor),
+            ExtractorKind::Auto => match base_url.host_str() {
+                Some("web.sas.upenn.edu") => Box::new(upenn::UpennExtractor),
+                _ => Box::new(generic::GenericExtractor),
+            },
+        }
+    }
+}
+
+/// Resolves a scraped `href` against the page it was found on.
+///
+/// Returns `None` (rather than erroring) for hrefs that can't be resolved to
+/// a URL at all, e.g. a malformed host like `http://[::1`, so callers can
+/// simply filter them out. Hrefs with their own scheme, such as
+/// `javascript:void(0)`, parse as absolute URLs and are returned as is.
+pub(crate) fn resolve_href(base_url: &Url, href: &str) -> Option<Url> {
+    base_url.join(href).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_href_resolves_relative_path() {
+        let base = Url::parse("https://example.com/albums/1/").unwrap();
+        assert_eq!(
+            resolve_href(&base, "photo.jpg").unwrap().as_str(),
+            "https://example.com/albums/1/photo.jpg"
+        );
+    }
+
+    #[test]
+    fn resolve_href_keeps_absolute_url_unchanged() {
+        let base = Url::parse("https://example.com/albums/1/").unwrap();
+        assert_eq!(
+            resolve_href(&base, "https://other.com/file.zip")
+                .unwrap()
+                .as_str(),
+            "https://other.com/file.zip"
+        );
+    }
+
+    #[test]
+    fn resolve_href_returns_none_for_unresolvable_href() {
+        let base = Url::parse("https://example.com/albums/1/").unwrap();
+        assert!(resolve_href(&base, "http://[::1").is_none());
+    }
+}
@@ -0,0 +1,39 @@
+use std::collections::BTreeSet;
+
+use async_trait::async_trait;
+use scraper::{Html, Selector};
+use url::Url;
+
+use crate::link::Link;
+
+use super::{resolve_href, Extractor};
+
+/// Extractor for the UPenn album pages (`web.sas.upenn.edu/upennidb/albums/`),
+/// which lay out each file as an `<a>` paired with a `<strong>` caption inside
+/// a `.nidb-album` container.
+pub struct UpennExtractor;
+
+#[async_trait(?Send)]
+impl Extractor for UpennExtractor {
+    async fn extract(&self, html: &Html, base_url: &Url) -> anyhow::Result<BTreeSet<Link>> {
+        let selector =
+            Selector::parse(".nidb-album a").expect("Could not create '.nidb-album a' selector");
+        let urls = html
+            .select(&selector)
+            .filter_map(|el| el.value().attr("href"))
+            .filter_map(|href| resolve_href(base_url, href));
+
+        let selector = Selector::parse(".nidb-album p > strong")
+            .expect("Coud not create '.nidb-album p > strong' selector");
+        let names = html.select(&selector).map(|el| el.inner_html());
+
+        Ok(urls
+            .zip(names)
+            .map(|(url, name)| Link {
+                url,
+                name,
+                checksum: None,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashSet, path::Path};
+
+use percent_encoding::percent_decode_str;
+use reqwest::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use reqwest::Response;
+use tokio::sync::Mutex;
+use url::Url;
+
+/// Maps a handful of common MIME types to a file extension, for when a file
+/// has no name-derived extension to fall back on.
+const MIME_EXTENSIONS: &[(&str, &str)] = &[
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+    ("image/tiff", "tiff"),
+    ("image/bmp", "bmp"),
+    ("application/pdf", "pdf"),
+    ("application/zip", "zip"),
+    ("application/gzip", "gz"),
+    ("application/x-tar", "tar"),
+    ("application/msword", "doc"),
+    ("application/vnd.ms-excel", "xls"),
+    ("text/plain", "txt"),
+    ("text/csv", "csv"),
+    ("video/mp4", "mp4"),
+    ("audio/mpeg", "mp3"),
+];
+
+/// Whether `name` already looks like a usable file name, i.e. it is
+/// non-empty and its last component has a (non-empty) extension.
+pub fn has_extension(name: &str) -> bool {
+    !name.is_empty()
+        && Path::new(name)
+            .extension()
+            .is_some_and(|ext| !ext.is_empty())
+}
+
+/// Resolves the file name to save `url` under, preferring (in order):
+/// `name` if it already looks usable, the `Content-Disposition` filename,
+/// the last URL path segment, then falling back to `Content-Type` to guess
+/// an extension. The result is percent-decoded and sanitized so it can't
+/// escape the output directory.
+pub fn resolve(name: &str, url: &Url, head: Option<&Response>) -> String {
+    if has_extension(name) {
+        return sanitize(name);
+    }
+
+    let from_disposition = head.and_then(content_disposition_filename);
+    let from_url = last_path_segment(url);
+
+    let mut candidate = match (from_disposition, from_url) {
+        (Some(name), _) if !name.is_empty() => name,
+        (_, Some(name)) if !name.is_empty() => name,
+        _ => name.to_owned(),
+    };
+
+    if candidate.is_empty() {
+        candidate = "download".to_owned();
+    }
+
+    if !has_extension(&candidate) {
+        if let Some(ext) = head.and_then(content_type_extension) {
+            candidate = format!("{candidate}.{ext}");
+        }
+    }
+
+    sanitize(&candidate)
+}
+
+/// Percent-decodes `name` and strips anything that could be used to escape
+/// the output directory (path separators, `..` components).
+pub fn sanitize(name: &str) -> String {
+    let decoded = percent_decode_str(name).decode_utf8_lossy();
+
+    decoded
+        .chars()
+        .map(|c| if c == '/' || c == '\\' { '_' } else { c })
+        .collect::<String>()
+        .replace("..", "_")
+}
+
+/// Claims a unique name within `claimed`, appending ` (1)`, ` (2)`, ... to
+/// the file stem until a free one is found, so concurrent downloads that
+/// resolve to the same name don't clobber each other.
+pub async fn claim_unique(name: String, claimed: &Mutex<HashSet<String>>) -> String {
+    let mut claimed = claimed.lock().await;
+
+    if claimed.insert(name.clone()) {
+        return name;
+    }
+
+    let path = Path::new(&name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let ext = path.extension().map(|e| e.to_string_lossy().into_owned());
+
+    let mut attempt = 1u32;
+    loop {
+        let candidate = match &ext {
+            Some(ext) => format!("{stem} ({attempt}).{ext}"),
+            None => format!("{stem} ({attempt})"),
+        };
+
+        if claimed.insert(candidate.clone()) {
+            return candidate;
+        }
+
+        attempt += 1;
+    }
+}
+
+fn content_disposition_filename(resp: &Response) -> Option<String> {
+    let raw = resp.headers().get(CONTENT_DISPOSITION)?.to_str().ok()?;
+
+    raw.split(';').map(str::trim).find_map(|part| {
+        part.strip_prefix("filename=")
+            .or_else(|| part.strip_prefix("filename*=UTF-8''"))
+            .map(|name| name.trim_matches('"').to_owned())
+    })
+}
+
+fn content_type_extension(resp: &Response) -> Option<&'static str> {
+    let raw = resp.headers().get(CONTENT_TYPE)?.to_str().ok()?;
+    let mime = raw.split(';').next()?.trim();
+
+    MIME_EXTENSIONS
+        .iter()
+        .find(|(candidate, _)| *candidate == mime)
+        .map(|(_, ext)| *ext)
+}
+
+fn last_path_segment(url: &Url) -> Option<String> {
+    let segment = url.path_segments()?.next_back()?;
+    (!segment.is_empty()).then(|| segment.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_replaces_path_separators() {
+        assert_eq!(sanitize("a/b\\c"), "a_b_c");
+    }
+
+    #[test]
+    fn sanitize_strips_parent_dir_components() {
+        assert!(!sanitize("../../etc/passwd").contains(".."));
+    }
+
+    #[test]
+    fn sanitize_percent_decodes() {
+        assert_eq!(sanitize("space%20name.txt"), "space name.txt");
+    }
+
+    #[test]
+    fn resolve_prefers_name_when_already_usable() {
+        let url = Url::parse("https://example.com/download?id=1").unwrap();
+        assert_eq!(resolve("photo.jpg", &url, None), "photo.jpg");
+    }
+
+    #[test]
+    fn resolve_falls_back_to_last_path_segment() {
+        let url = Url::parse("https://example.com/files/report.pdf").unwrap();
+        assert_eq!(resolve("", &url, None), "report.pdf");
+    }
+
+    #[test]
+    fn resolve_falls_back_to_download_when_nothing_usable() {
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(resolve("", &url, None), "download");
+    }
+
+    #[tokio::test]
+    async fn claim_unique_returns_name_unchanged_when_free() {
+        let claimed = Mutex::new(HashSet::new());
+        assert_eq!(claim_unique("foo.zip".to_owned(), &claimed).await, "foo.zip");
+    }
+
+    #[tokio::test]
+    async fn claim_unique_appends_counter_on_collision() {
+        let claimed = Mutex::new(HashSet::new());
+        assert_eq!(claim_unique("foo.zip".to_owned(), &claimed).await, "foo.zip");
+        assert_eq!(
+            claim_unique("foo.zip".to_owned(), &claimed).await,
+            "foo (1).zip"
+        );
+        assert_eq!(
+            claim_unique("foo.zip".to_owned(), &claimed).await,
+            "foo (2).zip"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+use url::Url;
+
+/// A single downloadable file discovered on a scraped page.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Link {
+    pub url: Url,
+    pub name: String,
+    /// Expected SHA-256 digest of the file contents, hex-encoded, if one is
+    /// known. The built-in extractors leave it `None`; `main` fills it in
+    /// from the `--checksums` sidecar file.
+    pub checksum: Option<String>,
+}
@@ -1,35 +1,54 @@
-use std::{
-    cmp::min,
-    collections::BTreeSet,
-    path::{Path, PathBuf},
-    sync::Arc,
-};
+use std::{collections::BTreeSet, collections::HashSet, path::PathBuf, sync::Arc, time::Instant};
 
 use clap::Parser;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use scraper::{Html, Selector};
-use tokio::{fs::File, io::AsyncWriteExt, sync::Semaphore, task::JoinSet};
-
-use futures_util::StreamExt;
-
-/// Simple program to greet a person
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use scraper::Html;
+use tokio::{sync::Mutex, sync::Semaphore, task::JoinSet};
+use url::Url;
+
+mod checksums;
+mod download;
+mod error;
+mod extractor;
+mod filename;
+mod link;
+
+use download::{download_file_to, get_headers, DownloadStatus, OverwritePolicy};
+use extractor::ExtractorKind;
+
+/// CLI arguments for neboder.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Name of the person to greet
+    /// Page to scrape for downloadable files
+    url: Url,
+
+    /// Directory to place downloaded files in
     #[arg(short, long)]
     output_dir: String,
 
     /// Number of parallel downloads
-    #[arg(short, default_value_t = 5)]
-    num_of_lanes: u8,
-}
-
-#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
-struct Link {
-    url: String,
-    name: String,
+    #[arg(short, default_value_t = 8)]
+    num_of_lanes: u32,
+
+    /// Which extractor to use for finding links on the target page
+    #[arg(short, long, value_enum, default_value_t = ExtractorKind::Auto)]
+    extractor: ExtractorKind,
+
+    /// Number of times to retry a download after a transient failure
+    /// (connection resets, timeouts, 5xx responses, or integrity mismatches)
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+
+    /// Whether to clobber files that already exist in the output directory
+    #[arg(long, value_enum, default_value_t = OverwritePolicy::Never)]
+    overwrite: OverwritePolicy,
+
+    /// Path to a `sha256sum`-style file (`<hex digest>  <name>` per line)
+    /// mapping scraped file names to their expected SHA-256 digest; matching
+    /// links are verified against it after download
+    #[arg(long)]
+    checksums: Option<PathBuf>,
 }
 
 #[tokio::main]
@@ -39,7 +58,7 @@ async fn main() -> anyhow::Result<()> {
     let client = reqwest::Client::new();
 
     let site_html = client
-        .get("https://web.sas.upenn.edu/upennidb/albums/")
+        .get(_args.url.clone())
         .headers(get_headers())
         .send()
         .await?
@@ -47,7 +66,20 @@ async fn main() -> anyhow::Result<()> {
         .await?;
 
     let html = Html::parse_document(&site_html);
-    let mut _links = collect_links(&html)?;
+    let extractor = _args.extractor.resolve(&_args.url);
+    let _links = extractor.extract(&html, &_args.url).await?;
+
+    let expected_checksums = match &_args.checksums {
+        Some(path) => checksums::load(path).await?,
+        None => Default::default(),
+    };
+    let _links: BTreeSet<_> = _links
+        .into_iter()
+        .map(|mut link| {
+            link.checksum = expected_checksums.get(&link.name).cloned();
+            link
+        })
+        .collect();
 
     let dir = PathBuf::from(_args.output_dir);
     if !dir.exists() {
@@ -57,95 +89,78 @@ async fn main() -> anyhow::Result<()> {
             .await?;
     }
 
+    let total_links = _links.len() as u64;
+
     let mb = MultiProgress::new();
     let p_sty = ProgressStyle::default_bar()
-        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")?
+        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {binary_bytes}/{binary_total_bytes} ({binary_bytes_per_sec}, {eta})")?
         .progress_chars("#>-");
 
-    let mut join_set: JoinSet<anyhow::Result<()>> = JoinSet::new();
+    let overall_pb = mb.add(ProgressBar::new(total_links));
+    overall_pb.set_style(
+        ProgressStyle::default_bar()
+            .template("overall [{elapsed_precise}] [{wide_bar:.magenta/blue}] {pos}/{len} files")?
+            .progress_chars("#>-"),
+    );
+
+    let started_at = Instant::now();
 
-    let semaphore = Arc::new(Semaphore::new(_args.num_of_lanes.into()));
+    let mut join_set: JoinSet<anyhow::Result<download::DownloadOutcome>> = JoinSet::new();
+
+    let semaphore = Arc::new(Semaphore::new(_args.num_of_lanes as usize));
+    let claimed_names = Arc::new(Mutex::new(HashSet::new()));
 
     for link in _links {
         let dir = dir.clone();
         let mb = mb.clone();
         let p_sty = p_sty.clone();
+        let claimed_names = Arc::clone(&claimed_names);
+        let max_retries = _args.max_retries;
+        let overwrite = _args.overwrite;
 
         let permit = Arc::clone(&semaphore).acquire_owned().await;
 
         join_set.spawn(async move {
             let _permit = permit;
-            download_file_to(&link, dir, mb, p_sty).await
+            download_file_to(&link, dir, mb, p_sty, claimed_names, max_retries, overwrite).await
         });
     }
 
+    let mut exists = 0u32;
+    let mut resumed = 0u32;
+    let mut downloaded = 0u32;
+    let mut failed = 0u32;
+    let mut total_bytes_transferred = 0u64;
+
     while let Some(r) = join_set.join_next().await {
-        println!("Result of download: {r:?}");
+        match r? {
+            Ok(outcome) => {
+                total_bytes_transferred += outcome.bytes_transferred;
+                match outcome.status {
+                    DownloadStatus::Exists => exists += 1,
+                    DownloadStatus::Resumed => resumed += 1,
+                    DownloadStatus::Downloaded => downloaded += 1,
+                }
+            }
+            Err(err) => {
+                failed += 1;
+                eprintln!("download failed: {err:?}");
+            }
+        }
+
+        overall_pb.inc(1);
     }
 
-    Ok(())
-}
-
-async fn download_file_to(
-    link: &Link,
-    dir: impl AsRef<Path>,
-    mb: MultiProgress,
-    p_sty: ProgressStyle,
-) -> anyhow::Result<()> {
-    let client = reqwest::Client::new();
-
-    let resp = client.get(&link.url).headers(get_headers()).send().await?;
-
-    let total_size = resp.content_length().unwrap_or(0);
-    let msg: &'static str = Box::leak::<'static>(Box::new(format!("Downloading {}", link.name)));
-    let pb = mb.add(ProgressBar::new(total_size));
-    pb.set_style(p_sty);
-    pb.set_message(msg);
-
-    let file_path = PathBuf::from(dir.as_ref()).join(Path::new(&link.name));
-
-    let mut file = File::create(file_path).await?;
-    let mut downloaded = 0u64;
-    let mut stream = resp.bytes_stream();
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk?;
-        file.write_all(&chunk).await?;
-
-        let new = min(downloaded + (chunk.len() as u64), total_size);
-        downloaded = new;
-        pb.set_position(new);
-    }
+    overall_pb.finish_with_message("all files processed");
 
-    pb.finish_with_message("done");
+    println!(
+        "done: {downloaded} downloaded, {resumed} resumed, {exists} already complete, {failed} failed"
+    );
+    println!(
+        "transferred {} in {:.1?}",
+        HumanBytes(total_bytes_transferred),
+        started_at.elapsed()
+    );
 
     Ok(())
 }
-
-fn collect_links(doc: &Html) -> anyhow::Result<BTreeSet<Link>> {
-    let selector =
-        Selector::parse(".nidb-album a").expect("Could not create '.nidb-album a' selector");
-    let urls = doc
-        .select(&selector)
-        .filter_map(|el| el.value().attr("href").map(String::from));
-
-    let selector = Selector::parse(".nidb-album p > strong")
-        .expect("Coud not create '.nidb-album p > strong' selector");
-    let names = doc.select(&selector).map(|el| el.inner_html());
-
-    Ok(urls
-        .zip(names)
-        .map(|(url, name)| Link { url, name })
-        .collect())
-}
-
-fn get_headers() -> HeaderMap {
-    let mut headers = HeaderMap::new();
-
-    // pretend we're a browser
-    headers.insert(
-        HeaderName::from_static("user-agent"),
-        HeaderValue::from_static("Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/112.0.0.0 Safari/537.36"));
-
-    headers
-}